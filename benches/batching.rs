@@ -0,0 +1,90 @@
+//! Compare the time and the connections needed to deliver a burst of reports,
+//! with and without batching, against a local keep-alive server.
+//!
+//! Run with `cargo bench --bench batching`.
+
+extern crate rollbar;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const REPORTS: usize = 500;
+
+/// Start a server that keeps the connections alive, and return its endpoint
+/// along with the number of connections it accepted.
+fn server() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/api/1/item/", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    let counter = connections.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+
+                        let line = line.to_lowercase();
+                        if let Some(value) = line.strip_prefix("content-length:") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+
+                    let mut body = vec![0; content_length];
+                    if reader.read_exact(&mut body).is_err()
+                        || write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").is_err()
+                    {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    (endpoint, connections)
+}
+
+fn run(name: &str, batching: Option<(usize, Duration)>) {
+    let (endpoint, connections) = server();
+    let mut client = rollbar::Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+    client.with_endpoint(endpoint);
+    if let Some((max, linger)) = batching {
+        client.with_batching(max, linger);
+    }
+
+    let started = Instant::now();
+    for _ in 0..REPORTS {
+        let _ = client.build_report().from_message("hai").send();
+        thread::sleep(Duration::from_micros(200));
+    }
+    client.flush(Duration::from_secs(60));
+
+    println!(
+        "{}: {} reports in {:?} over {} connections",
+        name,
+        REPORTS,
+        started.elapsed(),
+        connections.load(Ordering::SeqCst)
+    );
+}
+
+fn main() {
+    run("without batching", None);
+    run("with batching", Some((50, Duration::from_millis(20))));
+}
@@ -0,0 +1,44 @@
+extern crate http;
+extern crate rollbar;
+
+use http::{Request, Response, StatusCode};
+
+/// A handler failing on every request, as returned by the routes of a web framework.
+fn handler(_request: &Request<()>) -> Result<Response<()>, std::num::ParseIntError> {
+    let id = "笑".parse::<i32>()?;
+    println!("user #{}", id);
+    Ok(Response::new(()))
+}
+
+/// A middleware reporting to Rollbar the errors returned by `handler`,
+/// along with the request that caused them.
+/// The middlewares of `axum`, `actix-web` and friends work the same way.
+fn report_errors<F, E>(client: &rollbar::Client, request: &Request<()>, handler: F) -> Response<()>
+where
+    F: Fn(&Request<()>) -> Result<Response<()>, E>,
+    E: std::error::Error,
+{
+    handler(request).unwrap_or_else(|error| {
+        // join the thread only for testing purposes
+        let _ = client
+            .build_report()
+            .from_http_error(request, &error)
+            .send()
+            .join();
+
+        let mut response = Response::new(());
+        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        response
+    })
+}
+
+fn main() {
+    let client = rollbar::Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+    let request = Request::get("/users/笑")
+        .header("User-Agent", "curl/8.0")
+        .body(())
+        .unwrap();
+
+    let response = report_errors(&client, &request, handler);
+    println!("{}", response.status());
+}
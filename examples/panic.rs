@@ -7,12 +7,15 @@ fn main() {
     report_panics!(client);
 
     /* // `report_panics!` expands to the following code:
+     * let previous_hook = std::panic::take_hook();
      * std::panic::set_hook(Box::new(move |panic_info| {
      *     let backtrace = backtrace::Backtrace::new();
-     *     client.build_report()
+     *     let _ = client.build_report()
      *         .from_panic(panic_info)
      *         .with_backtrace(&backtrace)
-     *         .send();
+     *         .send()
+     *         .join();
+     *     previous_hook(panic_info);
      * }));
      * // If you want to customize the reports, you might not want to use the macro.
      * // Join the thread only for testing purposes.
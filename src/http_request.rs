@@ -0,0 +1,198 @@
+//! Describe the requests of the web frameworks built on top of `http` (e.g. `axum`),
+//! to report the errors of their handlers along with the request that caused them.
+//!
+//! This is `http` 1.x, the one of today's web frameworks: its `Request` is not the one
+//! of the `http` 0.1 `hyper` 0.12 is built on, through which the reports are sent.
+
+use std::error;
+use std::net::SocketAddr;
+
+use http::Request;
+
+use {OwnedReportBuilder, ReportBuilder, ReportErrorBuilder, RequestInfo};
+
+impl RequestInfo {
+    /// Describe `request` with its method, its URI, its query string and its headers.
+    ///
+    /// The IP address of the user is the one of `peer_addr`, the other end of the connection.
+    /// See `RequestInfo::from_http_behind_proxy` when it is a proxy.
+    /// The URI of the requests received by a server is usually made only of a path
+    /// and of a query string.
+    ///
+    /// Available with the `http` feature.
+    pub fn from_http<B>(request: &Request<B>, peer_addr: Option<SocketAddr>) -> Self {
+        let mut request_info = RequestInfo::describe(request);
+        if let Some(peer_addr) = peer_addr {
+            request_info.with_user_ip(peer_addr.ip().to_string());
+        }
+
+        request_info
+    }
+
+    /// Describe `request` as `RequestInfo::from_http` does, for a server behind proxies:
+    /// the IP address of the user is the first one in the `X-Forwarded-For` header,
+    /// or the one in the `X-Real-IP` header, as set by the proxies. Without them,
+    /// it is the one of `peer_addr`.
+    ///
+    /// Anybody can send these headers: use it only when the proxies in front of the server
+    /// overwrite them.
+    ///
+    /// Available with the `http` feature.
+    pub fn from_http_behind_proxy<B>(request: &Request<B>, peer_addr: Option<SocketAddr>) -> Self {
+        let mut request_info = RequestInfo::describe(request);
+        let header = |name: &str| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let user_ip = header("x-forwarded-for")
+            .and_then(|ips| ips.split(',').next())
+            .or_else(|| header("x-real-ip"))
+            .map(|ip| ip.trim().to_owned())
+            .filter(|ip| !ip.is_empty())
+            .or_else(|| peer_addr.map(|addr| addr.ip().to_string()));
+        if let Some(user_ip) = user_ip {
+            request_info.with_user_ip(user_ip);
+        }
+
+        request_info
+    }
+
+    /// Function used internally to describe `request`, without the IP address of the user.
+    fn describe<B>(request: &Request<B>) -> Self {
+        let mut request_info = RequestInfo::new();
+        request_info
+            .with_url(request.uri().to_string())
+            .with_method(request.method().as_str());
+
+        if let Some(query_string) = request.uri().query() {
+            request_info.with_query_string(query_string);
+        }
+
+        for name in request.headers().keys() {
+            let values: Vec<&str> = request
+                .headers()
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect();
+            if !values.is_empty() {
+                request_info.with_header(name.as_str(), values.join(", "));
+            }
+        }
+
+        request_info
+    }
+}
+
+impl<'a, B> From<&'a Request<B>> for RequestInfo {
+    /// See `RequestInfo::from_http`, without knowing the other end of the connection.
+    fn from(request: &'a Request<B>) -> Self {
+        RequestInfo::from_http(request, None)
+    }
+}
+
+impl<'a> ReportBuilder<'a> {
+    /// To be used when an `error::Error` returned while handling `request` must be reported,
+    /// e.g. from a middleware. The request is attached to the report as `RequestInfo`.
+    ///
+    /// Available with the `http` feature.
+    pub fn from_http_error<B, E: error::Error>(
+        &'a mut self,
+        request: &Request<B>,
+        error: &'a E,
+    ) -> ReportErrorBuilder<'a> {
+        let mut report = self.from_error(error);
+        report.with_request(RequestInfo::from(request));
+        report
+    }
+}
+
+impl OwnedReportBuilder {
+    /// To be used when an `error::Error` returned while handling `request` must be reported.
+    /// See `ReportBuilder::from_http_error`.
+    ///
+    /// Available with the `http` feature.
+    pub fn from_http_error<B, E: error::Error>(
+        self,
+        request: &Request<B>,
+        error: &E,
+    ) -> ReportErrorBuilder<'static> {
+        let mut report = self.from_error(error);
+        report.with_request(RequestInfo::from(request));
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use http::Request;
+
+    use {Client, RequestInfo};
+
+    #[test]
+    fn test_from_http() {
+        let request = Request::post("/users/42?expand=true")
+            .header("Host", "example.com")
+            .header("Accept", "text/html")
+            .header("Accept", "application/json")
+            .header("Cookie", "session=secret")
+            .body(())
+            .unwrap();
+        let peer_addr: SocketAddr = "10.0.0.1:54321".parse().unwrap();
+
+        let client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+        let error = "x".parse::<i32>().unwrap_err();
+        let mut report_builder = client.build_report();
+        let mut report = report_builder.from_http_error(&request, &error);
+        report.with_request(RequestInfo::from_http(&request, Some(peer_addr)));
+        let payload = report.to_value();
+        assert_eq!(
+            payload["data"]["request"],
+            json!({
+                "url": "/users/42?expand=true",
+                "method": "POST",
+                "query_string": "expand=true",
+                "headers": {
+                    "accept": "text/html, application/json",
+                    "cookie": "session=secret",
+                    "host": "example.com"
+                },
+                "user_ip": "10.0.0.1"
+            })
+        );
+
+        let request = Request::get("/")
+            .header("X-Forwarded-For", "203.0.113.7, 10.0.0.1")
+            .header("X-Real-IP", "10.0.0.1")
+            .body(())
+            .unwrap();
+        let payload = client
+            .build_report_owned()
+            .from_http_error(&request, &error)
+            .to_value();
+        assert_eq!(payload["data"]["request"].get("user_ip"), None);
+        assert_eq!(
+            payload["data"]["body"]["trace"]["exception"]["message"],
+            "invalid digit found in string"
+        );
+
+        // the headers can be forged unless the proxies in front of the server overwrite them
+        let request_info = RequestInfo::from_http(&request, Some(peer_addr));
+        assert_eq!(request_info.user_ip, Some("10.0.0.1".to_owned()));
+        let request_info = RequestInfo::from_http_behind_proxy(&request, Some(peer_addr));
+        assert_eq!(request_info.user_ip, Some("203.0.113.7".to_owned()));
+
+        let request = Request::get("/")
+            .header("X-Real-IP", "198.51.100.2")
+            .body(())
+            .unwrap();
+        let request_info = RequestInfo::from_http_behind_proxy(&request, Some(peer_addr));
+        assert_eq!(request_info.user_ip, Some("198.51.100.2".to_owned()));
+        let request_info = RequestInfo::from_http_behind_proxy(&request, None);
+        assert_eq!(request_info.user_ip, Some("198.51.100.2".to_owned()));
+    }
+}
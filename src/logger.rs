@@ -0,0 +1,163 @@
+//! Forward the records logged through `log` to Rollbar.
+
+use log::{self, Log, Metadata, Record, SetLoggerError};
+
+use {is_internal_target, Client, FrameBuilder, Level};
+
+/// A `log::Log` implementation that reports to Rollbar the records at or above a given level.
+///
+/// Every record is reported with its target as class, and with a frame pointing to
+/// the module, the file and the line it was logged from.
+///
+/// Available with the `log` feature.
+pub struct RollbarLogger {
+    client: Client,
+    level: log::Level,
+}
+
+impl From<log::Level> for Level {
+    /// `Trace` and `Debug` both become `Level::DEBUG`.
+    fn from(level: log::Level) -> Level {
+        match level {
+            log::Level::Error => Level::ERROR,
+            log::Level::Warn => Level::WARNING,
+            log::Level::Info => Level::INFO,
+            log::Level::Debug | log::Level::Trace => Level::DEBUG,
+        }
+    }
+}
+
+impl RollbarLogger {
+    /// Create a new `RollbarLogger` reporting through `client` the records at or above `level`.
+    pub fn new(client: Client, level: log::Level) -> Self {
+        RollbarLogger { client, level }
+    }
+
+    /// Register a new `RollbarLogger` as the global logger.
+    pub fn init(client: Client, level: log::Level) -> Result<(), SetLoggerError> {
+        log::set_boxed_logger(Box::new(RollbarLogger::new(client, level)))?;
+        log::set_max_level(level.to_level_filter());
+        Ok(())
+    }
+}
+
+impl Log for RollbarLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // `log` considers the more verbose levels as the greater ones
+        metadata.level() <= self.level && !is_internal_target(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let level = Level::from(record.level());
+        let message = format!("{}", record.args());
+
+        let mut frame = FrameBuilder::new();
+        frame.with_file_name(record.file().unwrap_or_else(|| record.target()));
+        if let Some(line) = record.line() {
+            frame.with_line_number(line);
+        }
+        if let Some(module_path) = record.module_path() {
+            frame.with_function_name(module_path);
+        }
+
+        let mut report_builder = self.client.build_report();
+        let mut report = report_builder.from_error_message(&message);
+        report.trace.exception.class = record.target().to_owned();
+        report
+            .with_frame(frame.build())
+            .with_level(level)
+            .send()
+            .detach();
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use log::{self, Log, Metadata, Record};
+    use serde_json::{self, Value};
+
+    use super::RollbarLogger;
+    use tests::mock_server;
+    use {Client, Level};
+
+    #[test]
+    fn test_from_log_level() {
+        assert_eq!(Level::from(log::Level::Error), Level::ERROR);
+        assert_eq!(Level::from(log::Level::Warn), Level::WARNING);
+        assert_eq!(Level::from(log::Level::Info), Level::INFO);
+        assert_eq!(Level::from(log::Level::Debug), Level::DEBUG);
+        assert_eq!(Level::from(log::Level::Trace), Level::DEBUG);
+    }
+
+    #[test]
+    fn test_rollbar_logger() {
+        let (endpoint, payloads) = mock_server(200, 1);
+        let mut client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+        client.with_endpoint(endpoint);
+
+        let logger = RollbarLogger::new(client, log::Level::Warn);
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Info)
+                .target("app")
+                .args(format_args!("ignored"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Error)
+                .target("hyper::client")
+                .args(format_args!("ignored"))
+                .build(),
+        );
+        // the crates of the `rustls` build deliver the reports too
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Warn)
+                .target("rustls::conn")
+                .args(format_args!("ignored"))
+                .build(),
+        );
+        let metadata = |target| {
+            Metadata::builder()
+                .level(log::Level::Error)
+                .target(target)
+                .build()
+        };
+        assert!(!logger.enabled(&metadata("tokio_rustls")));
+        assert!(!logger.enabled(&metadata("h2::proto")));
+        assert!(logger.enabled(&metadata("h2o")));
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Warn)
+                .target("app::db")
+                .module_path(Some("app::db"))
+                .file(Some("src/db.rs"))
+                .line(Some(42))
+                .args(format_args!("slow query"))
+                .build(),
+        );
+
+        let payload = payloads.recv_timeout(Duration::from_secs(5)).unwrap();
+        let payload: Value = serde_json::from_str(&payload).unwrap();
+
+        let data = &payload["data"];
+        assert_eq!(data["level"], "warning");
+
+        let trace = &data["body"]["trace"];
+        assert_eq!(trace["exception"]["class"], "app::db");
+        assert_eq!(trace["exception"]["message"], "slow query");
+        assert_eq!(
+            trace["frames"][0],
+            json!({ "filename": "src/db.rs", "lineno": 42, "method": "app::db" })
+        );
+    }
+}
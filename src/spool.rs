@@ -0,0 +1,316 @@
+//! Keep on disk the reports that couldn't be delivered, to deliver them on a later run.
+
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The extension of the files holding a payload.
+const EXTENSION: &str = "json";
+
+/// Tells apart the files saved by a process within the same millisecond.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory holding the payloads that couldn't be delivered, one per file, named after
+/// when they have been saved. The files are only ever renamed or removed once written,
+/// so that several processes can share the same directory.
+///
+/// As the payloads contain the access token, on Unix the files can only be read by their
+/// owner, as well as the directory when it is created by the `Spool`.
+pub struct Spool {
+    pub dir: PathBuf,
+    /// How many bytes the payloads can take at most, the oldest ones being removed past that.
+    pub max_size: u64,
+    /// How long a payload is kept before being removed.
+    pub ttl: Duration,
+}
+
+/// A payload taken from a `Spool`, which no other process can take in the meantime.
+pub struct Entry {
+    path: PathBuf,
+    taken: PathBuf,
+    pub payload: String,
+}
+
+/// Function used internally to tell the current time, in milliseconds since the Unix epoch.
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl Spool {
+    /// Save `payload`, removing the oldest payloads when it doesn't fit otherwise.
+    pub fn save(&self, payload: &str) -> io::Result<()> {
+        let size = payload.len() as u64;
+        if size > self.max_size {
+            return Ok(());
+        }
+
+        let mut dir_builder = DirBuilder::new();
+        dir_builder.recursive(true);
+        #[cfg(unix)]
+        dir_builder.mode(0o700);
+        dir_builder.create(&self.dir)?;
+
+        let entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|&(_, size)| size).sum();
+        for (path, entry_size) in entries {
+            if total + size <= self.max_size {
+                break;
+            }
+            if fs::remove_file(path).is_ok() {
+                total -= entry_size;
+            }
+        }
+
+        // written aside and then renamed, so that a payload is never read half-written,
+        // while still named after when it has been saved, so that it expires if left behind
+        let name = format!(
+            "{:013}-{}-{:010}",
+            now_ms(),
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        );
+        let partial = self.dir.join(format!("{}.partial", name));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        options.open(&partial)?.write_all(payload.as_bytes())?;
+        fs::rename(&partial, self.dir.join(name).with_extension(EXTENSION))
+    }
+
+    /// Take the payloads saved so far, from the oldest one. Each one must then be either
+    /// removed or put back.
+    pub fn take(&self) -> io::Result<Vec<Entry>> {
+        let mut taken = Vec::new();
+        for (path, _) in self.entries()? {
+            let taken_path = path.with_extension(format!("{}.{}", EXTENSION, process::id()));
+            // the renaming fails when another process took the payload first
+            if fs::rename(&path, &taken_path).is_err() {
+                continue;
+            }
+
+            match fs::read_to_string(&taken_path) {
+                Ok(payload) => taken.push(Entry {
+                    path,
+                    taken: taken_path,
+                    payload,
+                }),
+                Err(_) => {
+                    let _ = fs::remove_file(&taken_path);
+                }
+            }
+        }
+
+        Ok(taken)
+    }
+
+    /// Function used internally to list the payloads along with their size, from the oldest
+    /// one, removing the expired ones. The payloads taken by a process that didn't put them
+    /// back, or left half-written, are removed once expired too.
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+        let oldest = now_ms().saturating_sub(self.ttl.as_millis() as u64);
+        let mut entries = Vec::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let saved_at = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.split('-').next())
+                .and_then(|saved_at| saved_at.parse::<u64>().ok());
+
+            // the other files are left alone, as they weren't saved by a `Spool`
+            let saved_at = match saved_at {
+                Some(saved_at) => saved_at,
+                None => continue,
+            };
+            if saved_at < oldest {
+                let _ = fs::remove_file(&path);
+            } else if path.extension().and_then(|extension| extension.to_str()) == Some(EXTENSION) {
+                let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                entries.push((path, size));
+            }
+        }
+
+        entries.sort();
+        Ok(entries)
+    }
+}
+
+impl Entry {
+    /// Remove the payload for good, e.g. once delivered.
+    pub fn remove(self) {
+        let _ = fs::remove_file(&self.taken);
+    }
+
+    /// Put the payload back, to be taken again later.
+    pub fn put_back(self) {
+        let _ = fs::rename(&self.taken, &self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use std::time::Duration;
+
+    use hyper;
+    use serde_json::{self, Value};
+
+    use super::Spool;
+    use {Client, MockTransport};
+
+    /// Return an empty directory, named after the test using it.
+    fn spool_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rollbar-spool-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_spool() {
+        let spool = Spool {
+            dir: spool_dir("spool"),
+            max_size: 10,
+            ttl: Duration::from_secs(60),
+        };
+        spool.save("1234").unwrap();
+        spool.save("5678").unwrap();
+        // too large to ever fit
+        spool.save("12345678901").unwrap();
+        // the oldest payload is removed to make room for this one
+        spool.save("abcd").unwrap();
+        fs::write(spool.dir.join("notes.txt"), "not a payload").unwrap();
+        // left half-written by a process that crashed
+        fs::write(
+            spool.dir.join("0000000000001-1-0000000000.partial"),
+            "{\"access_token\"",
+        )
+        .unwrap();
+
+        let entries = spool.take().unwrap();
+        let payloads: Vec<&str> = entries.iter().map(|entry| &entry.payload[..]).collect();
+        assert_eq!(payloads, vec!["5678", "abcd"]);
+        // expired long ago
+        assert!(!spool
+            .dir
+            .join("0000000000001-1-0000000000.partial")
+            .exists());
+        // the payloads cannot be taken twice
+        assert!(spool.take().unwrap().is_empty());
+
+        let mut entries = entries.into_iter();
+        entries.next().unwrap().remove();
+        entries.next().unwrap().put_back();
+        let entries = spool.take().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].payload, "abcd");
+        entries.into_iter().for_each(|entry| entry.put_back());
+
+        // the expired payloads are removed, unlike the files not saved by the spool
+        let spool = Spool {
+            ttl: Duration::from_secs(0),
+            ..spool
+        };
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(spool.take().unwrap().is_empty());
+        let files: Vec<_> = fs::read_dir(&spool.dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+
+        let _ = fs::remove_dir_all(&spool.dir);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_spool_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let spool = Spool {
+            dir: spool_dir("permissions"),
+            max_size: 10,
+            ttl: Duration::from_secs(60),
+        };
+        spool.save("1234").unwrap();
+
+        // the payloads contain the access token
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&spool.dir), 0o700);
+        for entry in fs::read_dir(&spool.dir).unwrap() {
+            assert_eq!(mode(&entry.unwrap().path()), 0o600);
+        }
+
+        let _ = fs::remove_dir_all(&spool.dir);
+    }
+
+    #[test]
+    fn test_replay_spool() {
+        let dir = spool_dir("replay");
+        let mut client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+        client
+            .with_transport(
+                MockTransport::new()
+                    .with_status(hyper::StatusCode::SERVICE_UNAVAILABLE)
+                    .to_owned(),
+            )
+            .with_retry(1, Default::default())
+            .with_spool_dir(dir.to_owned());
+
+        for message in &["hai", "again"] {
+            let _ = client.build_report().from_message(message).send().wait();
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // Rollbar is still down, so the reports are kept
+        assert_eq!(client.replay_spool(), 0);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        let transport = MockTransport::new();
+        client.with_transport(transport.to_owned());
+        assert_eq!(client.replay_spool(), 2);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        let messages: Vec<Value> = transport
+            .payloads()
+            .iter()
+            .map(|payload| serde_json::from_str::<Value>(payload).unwrap())
+            .map(|payload| payload["data"]["body"]["message"]["body"].to_owned())
+            .collect();
+        assert_eq!(messages, vec!["hai", "again"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_spool_limits() {
+        let dir = spool_dir("limits");
+        let mut client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+        client
+            .with_transport(
+                MockTransport::new()
+                    .with_status(hyper::StatusCode::SERVICE_UNAVAILABLE)
+                    .to_owned(),
+            )
+            .with_retry(1, Default::default())
+            // set before the directory, the limits still apply
+            .with_spool_limits(10, Duration::from_secs(60))
+            .with_spool_dir(dir.to_owned());
+
+        let _ = client.build_report().from_message("hai").send().wait();
+        // too large to ever fit
+        assert!(!dir.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,207 @@
+//! Forward the events recorded through `tracing` to Rollbar.
+
+use std::fmt;
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+use {is_internal_target, Client, FrameBuilder, Level};
+
+/// A `tracing_subscriber::Layer` that reports to Rollbar the events at or above a given level.
+///
+/// The message of the event becomes the message of the report, while its other fields
+/// are attached as `custom` data. When an `error.type` field is present, the event is
+/// reported as an error of that class rather than as a plain message.
+///
+/// As with `RollbarLogger`, the events of this crate and of the ones delivering the reports
+/// (e.g. `hyper`) are not reported, as each report would otherwise produce new ones.
+///
+/// Available with the `tracing` feature.
+pub struct RollbarLayer {
+    client: Client,
+    level: tracing::Level,
+}
+
+impl From<tracing::Level> for Level {
+    /// `TRACE` and `DEBUG` both become `Level::DEBUG`.
+    fn from(level: tracing::Level) -> Level {
+        match level {
+            tracing::Level::ERROR => Level::ERROR,
+            tracing::Level::WARN => Level::WARNING,
+            tracing::Level::INFO => Level::INFO,
+            _ => Level::DEBUG,
+        }
+    }
+}
+
+impl RollbarLayer {
+    /// Create a new `RollbarLayer` reporting through `client` the events at or above `level`.
+    pub fn new(client: Client, level: tracing::Level) -> Self {
+        RollbarLayer { client, level }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for RollbarLayer {
+    fn on_event(&self, event: &Event, _ctx: Context<S>) {
+        let metadata = event.metadata();
+        // `tracing` considers the more verbose levels as the greater ones
+        if *metadata.level() > self.level || is_internal_target(metadata.target()) {
+            return;
+        }
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+
+        let level = Level::from(*metadata.level());
+        let message = visitor
+            .message
+            .unwrap_or_else(|| metadata.name().to_owned());
+        let class = visitor.fields.remove("error.type");
+        let custom = Value::Object(visitor.fields);
+
+        let mut report_builder = self.client.build_report();
+        match class {
+            Some(class) => {
+                let mut report = report_builder.from_error_message(&message);
+                report.trace.exception.class = match class {
+                    Value::String(class) => class,
+                    class => class.to_string(),
+                };
+
+                let mut frame = FrameBuilder::new();
+                frame.with_file_name(metadata.file().unwrap_or_else(|| metadata.target()));
+                if let Some(line) = metadata.line() {
+                    frame.with_line_number(line);
+                }
+
+                report
+                    .with_frame(frame.build())
+                    .with_level(level)
+                    .with_custom(custom)
+                    .send()
+                    .detach();
+            }
+            None => {
+                report_builder
+                    .from_message(&message)
+                    .with_level(level)
+                    .with_custom(custom)
+                    .send()
+                    .detach();
+            }
+        }
+    }
+}
+
+/// Collects the message and the other fields of an event.
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    fields: Map<String, Value>,
+}
+
+impl FieldVisitor {
+    fn record(&mut self, field: &Field, value: Value) {
+        if field.name() == "message" {
+            self.message = Some(match value {
+                Value::String(message) => message,
+                message => message.to_string(),
+            });
+        } else {
+            self.fields.insert(field.name().to_owned(), value);
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, json!(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, json!(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, json!(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::{self, Value};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::RollbarLayer;
+    use tests::mock_server;
+    use {Client, Level};
+
+    #[test]
+    fn test_from_tracing_level() {
+        assert_eq!(Level::from(tracing::Level::ERROR), Level::ERROR);
+        assert_eq!(Level::from(tracing::Level::WARN), Level::WARNING);
+        assert_eq!(Level::from(tracing::Level::INFO), Level::INFO);
+        assert_eq!(Level::from(tracing::Level::DEBUG), Level::DEBUG);
+        assert_eq!(Level::from(tracing::Level::TRACE), Level::DEBUG);
+    }
+
+    #[test]
+    fn test_rollbar_layer() {
+        let (endpoint, payloads) = mock_server(200, 2);
+        let mut client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+        client.with_endpoint(endpoint);
+
+        let subscriber =
+            tracing_subscriber::registry().with(RollbarLayer::new(client, tracing::Level::WARN));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app", "ignored");
+            // e.g. bridged from `log` through `tracing-log`
+            tracing::error!(target: "hyper::client", "ignored");
+            tracing::warn!(target: "app::db", attempt = 3, "slow query");
+            tracing::event!(
+                target: "app::db",
+                tracing::Level::ERROR,
+                error.type = "DatabaseError",
+                "cannot connect"
+            );
+        });
+
+        let mut payloads: Vec<Value> = (0..2)
+            .map(|_| payloads.recv_timeout(Duration::from_secs(5)).unwrap())
+            .map(|payload| serde_json::from_str(&payload).unwrap())
+            .collect();
+        payloads.sort_by_key(|payload| payload["data"]["level"].to_string());
+
+        let error = &payloads[0]["data"];
+        assert_eq!(error["level"], "error");
+        assert_eq!(
+            error["body"]["trace"]["exception"]["class"],
+            "DatabaseError"
+        );
+        assert_eq!(
+            error["body"]["trace"]["exception"]["message"],
+            "cannot connect"
+        );
+
+        let warning = &payloads[1]["data"];
+        assert_eq!(warning["level"], "warning");
+        assert_eq!(warning["body"]["message"]["body"], "slow query");
+        assert_eq!(warning["custom"]["attempt"], 3);
+    }
+}
@@ -0,0 +1,365 @@
+//! Deliver the payloads to Rollbar, over HTTP, in memory or nowhere at all.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::future;
+use hyper::client::connect::Connect;
+use hyper::client::ResponseFuture;
+use hyper::rt::{Future, Stream};
+use hyper::{self, header, Method, Request};
+use serde_json;
+
+use {dashboard_url, lock, ResponseStatus, RollbarError};
+
+/// How a `Client` delivers a payload to Rollbar, one attempt at a time.
+/// The timeout and the retries set on the `Client` are handled on top of it.
+///
+/// By default, the payloads are sent over HTTP through `hyper`.
+/// `MockTransport` keeps them in memory instead, e.g. for testing.
+pub trait Transport: Send + Sync {
+    /// Make an attempt to deliver `payload`, resolving to the status Rollbar answered with.
+    fn send(
+        &self,
+        payload: String,
+    ) -> Box<dyn Future<Item = ResponseStatus, Error = RollbarError> + Send>;
+}
+
+/// A `hyper::Client`, whatever its connector, through which the payloads are sent over HTTP.
+/// See `Client::with_http_client`.
+pub trait HyperClient: fmt::Debug + Send + Sync {
+    /// Send `request`, as `hyper::Client::request` does.
+    fn request(&self, request: Request<hyper::Body>) -> ResponseFuture;
+}
+
+impl<C> HyperClient for hyper::Client<C>
+where
+    C: Connect + 'static,
+    C::Transport: 'static,
+    C::Future: 'static,
+{
+    fn request(&self, request: Request<hyper::Body>) -> ResponseFuture {
+        hyper::Client::request(self, request)
+    }
+}
+
+impl<T: HyperClient + ?Sized> HyperClient for Arc<T> {
+    fn request(&self, request: Request<hyper::Body>) -> ResponseFuture {
+        (**self).request(request)
+    }
+}
+
+/// The default transport, sending the payloads to the endpoint of the `Client` over HTTP.
+pub struct HttpTransport {
+    pub http_client: Arc<dyn HyperClient>,
+    pub endpoint: String,
+    /// Whether the payloads are gzipped.
+    pub compression: bool,
+    /// The payload gzipped last, along with its compressed form, so that a payload
+    /// is compressed only once however many times its delivery is retried.
+    pub gzipped: Mutex<Option<(String, Vec<u8>)>>,
+}
+
+/// Function used internally to gzip a payload.
+fn gzip(payload: &str) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(payload.as_bytes())?;
+    encoder.finish()
+}
+
+impl HttpTransport {
+    /// Function used internally to gzip a payload, unless it is the one gzipped last.
+    fn gzip(&self, payload: &str) -> io::Result<Vec<u8>> {
+        let mut gzipped = lock(&self.gzipped);
+        if let Some((ref last, ref body)) = *gzipped {
+            if last == payload {
+                return Ok(body.to_owned());
+            }
+        }
+
+        let body = gzip(payload)?;
+        *gzipped = Some((payload.to_owned(), body.to_owned()));
+        Ok(body)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(
+        &self,
+        payload: String,
+    ) -> Box<dyn Future<Item = ResponseStatus, Error = RollbarError> + Send> {
+        let mut request = Request::builder();
+        request.method(Method::POST).uri(self.endpoint.as_str());
+
+        let body = if self.compression {
+            match self.gzip(&payload) {
+                Ok(body) => {
+                    request.header(header::CONTENT_ENCODING, "gzip");
+                    hyper::Body::from(body)
+                }
+                // not worth losing the report over
+                Err(_) => hyper::Body::from(payload),
+            }
+        } else {
+            hyper::Body::from(payload)
+        };
+
+        let request = match request.body(body) {
+            Ok(request) => request,
+            Err(error) => return Box::new(future::err(RollbarError::Build(error))),
+        };
+
+        let dashboard_url = dashboard_url(&self.endpoint);
+        let response = self.http_client.request(request).and_then(|res| {
+            let status_code = res.status();
+            let retry_after = res
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(Duration::from_secs);
+
+            res.into_body().concat2().map(move |body| ResponseStatus {
+                status_code,
+                body: serde_json::from_slice(&body).ok(),
+                retry_after,
+                dashboard_url,
+            })
+        });
+
+        Box::new(response.map_err(RollbarError::Http))
+    }
+}
+
+/// A transport that keeps the payloads in memory instead of sending them,
+/// answering to each one with the same status (`200 OK` by default).
+///
+/// Hand a clone of it to `Client::with_transport` and keep the original
+/// to inspect the payloads the `Client` sent.
+#[derive(Clone)]
+pub struct MockTransport {
+    payloads: Arc<Mutex<Vec<String>>>,
+    status_code: hyper::StatusCode,
+}
+
+impl Default for MockTransport {
+    fn default() -> Self {
+        MockTransport {
+            payloads: Arc::new(Mutex::new(Vec::new())),
+            status_code: hyper::StatusCode::OK,
+        }
+    }
+}
+
+impl MockTransport {
+    /// Create a new `MockTransport`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Answer to every payload with the given status.
+    pub fn with_status(&mut self, status_code: hyper::StatusCode) -> &mut Self {
+        self.status_code = status_code;
+        self
+    }
+
+    /// Return the payloads received so far, from the oldest one.
+    pub fn payloads(&self) -> Vec<String> {
+        match self.payloads.lock() {
+            Ok(payloads) => payloads.to_owned(),
+            Err(poisoned) => poisoned.into_inner().to_owned(),
+        }
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(
+        &self,
+        payload: String,
+    ) -> Box<dyn Future<Item = ResponseStatus, Error = RollbarError> + Send> {
+        match self.payloads.lock() {
+            Ok(mut payloads) => payloads.push(payload),
+            Err(poisoned) => poisoned.into_inner().push(payload),
+        }
+
+        Box::new(future::ok(ResponseStatus::from(self.status_code)))
+    }
+}
+
+/// A transport writing the pretty-printed payloads to `writer` instead of sending them,
+/// answering to each one with `200 OK`.
+pub struct DryRunTransport {
+    pub writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Transport for DryRunTransport {
+    fn send(
+        &self,
+        payload: String,
+    ) -> Box<dyn Future<Item = ResponseStatus, Error = RollbarError> + Send> {
+        let payload = serde_json::from_str::<serde_json::Value>(&payload)
+            .and_then(|payload| serde_json::to_string_pretty(&payload))
+            .unwrap_or(payload);
+
+        let mut writer = match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let _ = writeln!(writer, "{}", payload);
+
+        Box::new(future::ok(ResponseStatus::from(hyper::StatusCode::OK)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    use flate2::read::GzDecoder;
+    use hyper;
+    use serde_json::{self, Value};
+
+    use super::{HttpTransport, MockTransport};
+    use tests::mock_server_with;
+    use {Client, URL};
+
+    /// A writer that can still be read after having been handed to a `Client`.
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let writer = SharedWriter::default();
+        let mut transport = MockTransport::new();
+        let mut client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+        client
+            .with_transport(
+                transport
+                    .with_status(hyper::StatusCode::UNAUTHORIZED)
+                    .to_owned(),
+            )
+            .with_dry_run_writer(writer.to_owned());
+
+        let status = client
+            .build_report()
+            .from_message("hai")
+            .send()
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.status_code, hyper::StatusCode::OK);
+        assert!(transport.payloads().is_empty());
+
+        let output = String::from_utf8(writer.0.lock().unwrap().to_owned()).unwrap();
+        assert!(output.contains("\n  \"access_token\": \"ACCESS_TOKEN\""));
+        let payload: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(payload["data"]["body"]["message"]["body"], "hai");
+
+        client.with_dry_run(false);
+        let status = client
+            .build_report()
+            .from_message("hai")
+            .send()
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.status_code, hyper::StatusCode::UNAUTHORIZED);
+        assert_eq!(transport.payloads().len(), 1);
+    }
+
+    #[test]
+    fn test_compression() {
+        let (endpoint, requests) = mock_server_with(200, 1, |headers, body| (headers, body));
+        let mut client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+        client.with_endpoint(endpoint).with_compression(true);
+        let report = client.build_report().from_message("hai").to_string();
+        let status = client
+            .build_report()
+            .from_message("hai")
+            .send()
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.status_code, hyper::StatusCode::OK);
+
+        let (headers, body) = requests.recv().unwrap();
+        assert!(headers.contains(&"content-encoding: gzip".to_owned()));
+
+        let mut payload = String::new();
+        GzDecoder::new(&body[..])
+            .read_to_string(&mut payload)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&payload).unwrap(),
+            serde_json::from_str::<Value>(&report).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_compression_once() {
+        let transport = HttpTransport {
+            http_client: Client::new("ACCESS_TOKEN", "ENVIRONMENT").http_client(),
+            endpoint: URL.to_owned(),
+            compression: true,
+            gzipped: Mutex::new(None),
+        };
+
+        let mut body = Vec::new();
+        GzDecoder::new(&transport.gzip("hai").unwrap()[..])
+            .read_to_end(&mut body)
+            .unwrap();
+        assert_eq!(body, b"hai");
+
+        // the retries of a delivery send the body compressed by the first attempt
+        *transport.gzipped.lock().unwrap() = Some(("hai".to_owned(), b"gzipped".to_vec()));
+        assert_eq!(transport.gzip("hai").unwrap(), b"gzipped");
+        assert_ne!(transport.gzip("bye").unwrap(), b"gzipped");
+    }
+
+    #[test]
+    fn test_mock_transport() {
+        let mut transport = MockTransport::new();
+        transport.with_status(hyper::StatusCode::SERVICE_UNAVAILABLE);
+
+        let mut client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+        client
+            .with_transport(transport.to_owned())
+            .with_retry(3, Default::default());
+
+        let status = client
+            .build_report()
+            .from_error_message(&"w")
+            .send()
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            status.to_string(),
+            "Error 503 Service Unavailable: An undefined error occurred."
+        );
+
+        // every attempt goes through the transport
+        let payloads = transport.payloads();
+        assert_eq!(payloads.len(), 3);
+        let payload: Value = serde_json::from_str(&payloads[0]).unwrap();
+        assert_eq!(
+            payload["data"]["body"]["trace"]["exception"]["message"],
+            "w"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! `set_enabled` affects every `Client` of the process, so it is tested in a process of its own.
+
+extern crate rollbar;
+
+use rollbar::{Client, MockTransport, RollbarError};
+
+#[test]
+fn test_set_enabled() {
+    let transport = MockTransport::new();
+    let mut client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+    client.with_transport(transport.to_owned());
+    assert!(rollbar::is_enabled());
+
+    rollbar::set_enabled(false);
+    assert!(!rollbar::is_enabled());
+    let status = client
+        .build_report()
+        .from_message("hai")
+        .send()
+        .join()
+        .unwrap();
+    assert!(status.is_none());
+    match client
+        .build_report()
+        .from_error_message(&"w")
+        .send_result()
+        .join()
+        .unwrap()
+    {
+        Err(RollbarError::Disabled) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // the clients created in the meantime are disabled too
+    let mut other_client = Client::new("ACCESS_TOKEN", "ENVIRONMENT");
+    other_client.with_transport(transport.to_owned());
+    let status = other_client
+        .build_report()
+        .from_message("hai")
+        .send()
+        .join()
+        .unwrap();
+    assert!(status.is_none());
+    assert!(transport.payloads().is_empty());
+
+    rollbar::set_enabled(true);
+    let status = client
+        .build_report()
+        .from_message("hai")
+        .send()
+        .join()
+        .unwrap();
+    assert!(status.is_some());
+    assert_eq!(transport.payloads().len(), 1);
+}